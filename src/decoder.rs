@@ -0,0 +1,202 @@
+//! Incremental decoding for bencode read off a socket, where a full message
+//! may arrive split across several `recv` calls (and the next message's
+//! bytes may already be sitting right behind it in the same read).
+
+use std::collections::BTreeMap as Map;
+
+use crate::{BencodeType, ConvertError};
+
+/// An owned mirror of [`BencodeType`] that doesn't borrow from any input
+/// buffer.
+///
+/// [`Decoder::try_parse`] can't return zero-copy borrows the way [`crate::parse`]
+/// does: it drains a value's bytes from the internal buffer in the same call
+/// that returns the value, and later [`Decoder::feed`] calls may reallocate
+/// that buffer.
+#[derive(Debug, Eq, PartialEq)]
+pub enum BencodeOwned {
+    Integer(i64),
+    String(Vec<u8>),
+    List(Vec<BencodeOwned>),
+    Dictionary(Map<Vec<u8>, BencodeOwned>),
+}
+
+impl<'a> From<BencodeType<'a>> for BencodeOwned {
+    fn from(value: BencodeType<'a>) -> Self {
+        match value {
+            BencodeType::Integer(i) => BencodeOwned::Integer(i),
+            BencodeType::String(bytes) => BencodeOwned::String(bytes.to_vec()),
+            BencodeType::List(items) => {
+                BencodeOwned::List(items.into_iter().map(BencodeOwned::from).collect())
+            }
+            BencodeType::Dictionary(map) => BencodeOwned::Dictionary(
+                map.into_iter()
+                    .map(|(k, v)| (k.to_vec(), BencodeOwned::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Buffers bytes fed from a stream and hands back one top-level value at a
+/// time, once enough of it has arrived.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder { buf: Vec::new() }
+    }
+
+    /// Appends more bytes as they arrive off the wire.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns the next fully-buffered top-level value, if one is ready.
+    ///
+    /// `Ok(None)` means the buffer is empty or holds the start of a value but
+    /// not all of it yet; call [`Decoder::feed`] again and retry. Call this in a loop
+    /// after each `feed`, since more than one value may already be buffered
+    /// (e.g. pipelined peer-wire messages).
+    pub fn try_parse(&mut self) -> Result<Option<BencodeOwned>, ConvertError> {
+        let len = match scan_value(&self.buf)? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+
+        // `scan_value` only ever delimits a value's bytes; `parse` still does
+        // the real grammar checking (e.g. rejecting `i03e`), so a delimited
+        // slice can still come back as an error here.
+        let value = crate::parse(&self.buf[..len])?;
+        let owned = BencodeOwned::from(value);
+        self.buf.drain(..len);
+        Ok(Some(owned))
+    }
+}
+
+/// Finds how many bytes of `buf`, from the start, a complete top-level value
+/// would occupy, without fully parsing it. Returns `Ok(None)` if `buf` holds
+/// the start of a value but not all of it.
+pub(crate) fn scan_value(buf: &[u8]) -> Result<Option<usize>, ConvertError> {
+    match buf.first() {
+        None => Ok(None),
+        Some(b'i') => scan_terminated(buf),
+        Some(b'l') | Some(b'd') => scan_container(buf),
+        Some(b'0'..=b'9') => scan_string(buf),
+        Some(_) => Err(ConvertError::InvalidFormat),
+    }
+}
+
+/// Scans an integer: just the position of its closing `e`, since validating
+/// the digits in between is `parse`'s job once the full value has arrived.
+fn scan_terminated(buf: &[u8]) -> Result<Option<usize>, ConvertError> {
+    Ok(buf.iter().position(|&b| b == b'e').map(|idx| idx + 1))
+}
+
+/// Scans a length-prefixed string: `<digits>:<payload>`.
+fn scan_string(buf: &[u8]) -> Result<Option<usize>, ConvertError> {
+    let colon_idx = match buf.iter().position(|&b| b == b':') {
+        Some(idx) => idx,
+        None => return Ok(None),
+    };
+
+    let digits =
+        std::str::from_utf8(&buf[..colon_idx]).map_err(|_| ConvertError::InvalidEncoding)?;
+    let len = digits
+        .parse::<usize>()
+        .map_err(|_| ConvertError::PayloadTooBig)?;
+
+    // `len` comes straight from an untrusted peer; a declared length near
+    // `usize::MAX` must not be allowed to overflow this arithmetic and panic.
+    let total = colon_idx
+        .checked_add(1)
+        .and_then(|n| n.checked_add(len))
+        .ok_or(ConvertError::PayloadTooBig)?;
+    if buf.len() < total {
+        Ok(None)
+    } else {
+        Ok(Some(total))
+    }
+}
+
+/// Scans a list or dict by scanning its elements one at a time until the
+/// closing `e`, without caring whether it is a list or a dict (a dict's keys
+/// are just strings, scanned the same way as any other value).
+fn scan_container(buf: &[u8]) -> Result<Option<usize>, ConvertError> {
+    let mut pos = 1;
+    loop {
+        match buf.get(pos) {
+            None => return Ok(None),
+            Some(b'e') => return Ok(Some(pos + 1)),
+            Some(_) => match scan_value(&buf[pos..])? {
+                Some(len) => pos += len,
+                None => return Ok(None),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parses_once_fully_fed() {
+        let mut decoder = Decoder::new();
+        decoder.feed(b"i42e");
+        assert!(decoder.try_parse().unwrap() == Some(BencodeOwned::Integer(42)));
+    }
+
+    #[test]
+    fn waits_for_more_bytes() {
+        let mut decoder = Decoder::new();
+        decoder.feed(b"5:hel");
+        assert!(decoder.try_parse().unwrap().is_none());
+        decoder.feed(b"lo");
+        assert!(decoder.try_parse().unwrap() == Some(BencodeOwned::String(b"hello".to_vec())));
+    }
+
+    #[test]
+    fn waits_across_nested_containers() {
+        let mut decoder = Decoder::new();
+        decoder.feed(b"d3:fool");
+        assert!(decoder.try_parse().unwrap().is_none());
+        decoder.feed(b"i1ei2eee");
+        let mut expected = Map::new();
+        expected.insert(
+            b"foo".to_vec(),
+            BencodeOwned::List(vec![BencodeOwned::Integer(1), BencodeOwned::Integer(2)]),
+        );
+        assert!(decoder.try_parse().unwrap() == Some(BencodeOwned::Dictionary(expected)));
+    }
+
+    #[test]
+    fn handles_pipelined_messages() {
+        let mut decoder = Decoder::new();
+        decoder.feed(b"i1ei2e");
+        assert!(decoder.try_parse().unwrap() == Some(BencodeOwned::Integer(1)));
+        assert!(decoder.try_parse().unwrap() == Some(BencodeOwned::Integer(2)));
+        assert!(decoder.try_parse().unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_input_instead_of_waiting_forever() {
+        let mut decoder = Decoder::new();
+        decoder.feed(b"i03e");
+        decoder.try_parse().expect_err("leading zero is never valid");
+    }
+
+    #[test]
+    fn rejects_oversized_length_prefix_instead_of_panicking() {
+        let mut decoder = Decoder::new();
+        // a peer-supplied length near `usize::MAX` must error, not overflow
+        decoder.feed(b"18446744073709551615:x");
+        decoder
+            .try_parse()
+            .expect_err("declared length does not fit in memory");
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::{self, Write};
+
+use crate::BencodeType;
+
+/// Encodes a value into its canonical bencode representation.
+///
+/// Dictionary keys are always emitted in ascending byte order (the order a
+/// `BTreeMap` already iterates in), as required for the output to be
+/// byte-identical when a decoded value is re-encoded.
+pub fn encode(value: &BencodeType) -> Vec<u8> {
+    let mut buf = Vec::new();
+    encode_into(value, &mut buf).expect("writing to a Vec<u8> never fails");
+    buf
+}
+
+/// Like [`encode`], but writes directly into `writer` instead of allocating
+/// a `Vec<u8>`.
+pub fn encode_into<W: Write>(value: &BencodeType, writer: &mut W) -> io::Result<()> {
+    match value {
+        BencodeType::Integer(i) => write!(writer, "i{}e", i),
+        BencodeType::String(bytes) => {
+            write!(writer, "{}:", bytes.len())?;
+            writer.write_all(bytes)
+        }
+        BencodeType::List(items) => {
+            writer.write_all(b"l")?;
+            for item in items {
+                encode_into(item, writer)?;
+            }
+            writer.write_all(b"e")
+        }
+        BencodeType::Dictionary(map) => {
+            writer.write_all(b"d")?;
+            for (key, val) in map {
+                encode_into(&BencodeType::String(key), writer)?;
+                encode_into(val, writer)?;
+            }
+            writer.write_all(b"e")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::parse;
+    use std::collections::BTreeMap as Map;
+
+    #[test]
+    fn integer() {
+        assert!(encode(&BencodeType::Integer(12345)) == b"i12345e");
+        assert!(encode(&BencodeType::Integer(-12345)) == b"i-12345e");
+    }
+
+    #[test]
+    fn string() {
+        assert!(encode(&BencodeType::String(b"abc")) == b"3:abc");
+    }
+
+    #[test]
+    fn list() {
+        let list = BencodeType::List(vec![BencodeType::Integer(1), BencodeType::Integer(2)]);
+        assert!(encode(&list) == b"li1ei2ee");
+    }
+
+    #[test]
+    fn dict_keys_are_sorted() {
+        let mut map = Map::new();
+        map.insert(&b"zebra"[..], BencodeType::Integer(1));
+        map.insert(&b"apple"[..], BencodeType::Integer(2));
+        let dict = BencodeType::Dictionary(map);
+        assert!(encode(&dict) == b"d5:applei2e5:zebrai1ee");
+    }
+
+    #[test]
+    fn roundtrip() {
+        let bencode = b"d4:dictd3:key36:This is a string within a dictionarye7:integeri12345e4:listli1ei2ei3ei4e6:stringi5edee6:string11:Hello Worlde";
+
+        let value = parse(bencode).expect("this is a correct input");
+        assert!(encode(&value) == bencode[..]);
+    }
+}
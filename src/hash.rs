@@ -0,0 +1,50 @@
+//! Torrent `info_hash` support, enabled via the `hash` cargo feature.
+
+use sha1::{Digest, Sha1};
+
+use crate::span::dict_spans;
+use crate::ConvertError;
+
+/// Computes a torrent's `info_hash`: the SHA-1 digest of the exact source
+/// bytes of the top-level `info` dictionary entry.
+///
+/// This hashes the span [`dict_spans`] reports for `info` rather than a
+/// re-encoding of the parsed value, since the hash must match what every
+/// other client derives from the same `.torrent` file byte-for-byte; it
+/// never builds a parsed value tree for `info` (or any other entry) at all,
+/// since only its raw bytes are needed.
+pub fn info_hash(stream: &[u8]) -> Result<[u8; 20], ConvertError> {
+    if stream.first() != Some(&b'd') {
+        return Err(ConvertError::InvalidFormat);
+    }
+    let spans = dict_spans(stream)?;
+    let info = spans.get(&b"info"[..]).ok_or(ConvertError::InvalidFormat)?;
+
+    let mut hasher = Sha1::new();
+    hasher.update(info);
+    Ok(hasher.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn hashes_the_info_entry() {
+        let bencode = b"d4:infod6:lengthi10e4:name4:teste8:announce3:foee";
+        let hash = info_hash(bencode).expect("should parse and hash");
+
+        let mut hasher = Sha1::new();
+        hasher.update(b"d6:lengthi10e4:name4:teste");
+        let expected: [u8; 20] = hasher.finalize().into();
+
+        assert!(hash == expected);
+    }
+
+    #[test]
+    fn missing_info_is_an_error() {
+        let bencode = b"d8:announce3:fooe";
+        info_hash(bencode).expect_err("no info entry");
+    }
+}
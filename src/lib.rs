@@ -1,30 +1,64 @@
 extern crate ascii;
 
+use std::borrow::Cow;
 use std::collections::BTreeMap as Map;
 
+mod decoder;
+mod encode;
+#[cfg(feature = "hash")]
+mod hash;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod span;
+
+pub use decoder::{BencodeOwned, Decoder};
+pub use encode::{encode, encode_into};
+#[cfg(feature = "hash")]
+pub use hash::info_hash;
+#[cfg(feature = "serde")]
+pub use serde_impl::{from_bytes, to_bytes, to_writer, Deserializer, Serializer};
+pub use span::{parse_with_spans, SpanMap};
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum BencodeType<'a> {
-    Integer(i32),
-    String(&'a str),
+    Integer(i64),
+    String(&'a [u8]),
     List(Vec<BencodeType<'a>>),
-    Dictionary(Map<&'a str, BencodeType<'a>>),
+    Dictionary(Map<&'a [u8], BencodeType<'a>>),
 }
 
 impl<'a> BencodeType<'a> {
-    fn as_int(&self) -> Option<i32> {
+    fn as_int(&self) -> Option<i64> {
         match self {
             BencodeType::Integer(x) => Some(*x),
             _ => None,
         }
     }
 
-    fn as_str(&self) -> Option<&'a str> {
+    /// Returns the raw bytes of a bencoded string, whatever their encoding.
+    fn as_bytes(&self) -> Option<&'a [u8]> {
         match self {
             BencodeType::String(x) => Some(x),
             _ => None,
         }
     }
 
+    /// Returns the string only if the bytes are valid UTF-8.
+    fn as_str(&self) -> Option<&'a str> {
+        match self {
+            BencodeType::String(x) => std::str::from_utf8(x).ok(),
+            _ => None,
+        }
+    }
+
+    /// Like `as_str`, but replaces invalid UTF-8 sequences instead of failing.
+    fn as_str_lossy(&self) -> Option<Cow<'a, str>> {
+        match self {
+            BencodeType::String(x) => Some(String::from_utf8_lossy(x)),
+            _ => None,
+        }
+    }
+
     fn as_list(&self) -> Option<&[BencodeType]> {
         match self {
             BencodeType::List(ref x) => Some(x),
@@ -32,7 +66,7 @@ impl<'a> BencodeType<'a> {
         }
     }
 
-    fn as_dict(&self) -> Option<&Map<&str, BencodeType>> {
+    fn as_dict(&self) -> Option<&Map<&'a [u8], BencodeType<'a>>> {
         match self {
             BencodeType::Dictionary(ref x) => Some(x),
             _ => None,
@@ -86,10 +120,19 @@ fn parse_int(stream: &[u8]) -> Result<ParseResult, ConvertError> {
 
     let payload = &stream[1..e_idx];
     let ascii = ascii::AsciiStr::from_ascii(payload).map_err(|_| ConvertError::InvalidEncoding)?;
+    let digits = ascii.as_str();
+
+    // The spec forbids leading zeros (`i03e`) and negative zero (`i-0e`):
+    // both would let a value re-encode to different bytes than it was
+    // parsed from, which breaks info-hash stability.
+    let has_leading_zero = digits.len() > 1 && digits.starts_with('0');
+    let has_leading_zero_negative = digits.len() > 2 && digits.starts_with("-0");
+    if digits == "-0" || has_leading_zero || has_leading_zero_negative {
+        return Err(ConvertError::InvalidFormat);
+    }
 
-    let val = ascii
-        .as_str()
-        .parse::<i32>()
+    let val = digits
+        .parse::<i64>()
         .map_err(|_| ConvertError::PayloadTooBig)?;
     Ok(ParseResult::new(
         BencodeType::Integer(val),
@@ -113,16 +156,29 @@ fn parse_str(stream: &[u8]) -> Result<ParseResult, ConvertError> {
         ascii::AsciiStr::from_ascii(size_slice).map_err(|_| ConvertError::InvalidEncoding)?;
     let size = ascii
         .as_str()
-        .parse::<i32>()
-        .map_err(|_| ConvertError::PayloadTooBig)? as usize;
-
-    let payload_slice = &stream[colom_idx + 1..colom_idx + 1 + size];
+        .parse::<i64>()
+        .map_err(|_| ConvertError::PayloadTooBig)?;
+    if size < 0 {
+        return Err(ConvertError::InvalidFormat);
+    }
+    let size = size as usize;
+
+    // `size` comes straight from the input, which may be an attacker-
+    // controlled `.torrent` file or peer-wire message: a declared length
+    // past the end of `stream` must not be allowed to overflow this
+    // arithmetic or index out of bounds.
+    let payload_start = colom_idx + 1;
+    let payload_end = payload_start
+        .checked_add(size)
+        .ok_or(ConvertError::PayloadTooBig)?;
+    if payload_end > stream.len() {
+        return Err(ConvertError::BufferTooShort);
+    }
+    let payload_slice = &stream[payload_start..payload_end];
 
-    let ascii =
-        ascii::AsciiStr::from_ascii(payload_slice).map_err(|_| ConvertError::BufferTooShort)?;
     Ok(ParseResult::new(
-        BencodeType::String(ascii.as_str()),
-        &stream[colom_idx + 1 + size..],
+        BencodeType::String(payload_slice),
+        &stream[payload_end..],
     ))
 }
 
@@ -236,6 +292,15 @@ mod tests {
         assert!(int.value.as_int().unwrap() == 12345);
         assert!(int.next.len() != 0);
         assert!(int.next[0] == b's');
+
+        // values that overflow i32 but fit i64, e.g. file lengths
+        let int = parse_int(b"i8589934592e").expect("should convert");
+        assert!(int.value.as_int().unwrap() == 8_589_934_592);
+
+        parse_int(b"i03e").expect_err("leading zero");
+        parse_int(b"i-0e").expect_err("negative zero");
+        parse_int(b"i-03e").expect_err("leading zero, negative");
+        parse_int(b"i0e").expect("plain zero is valid");
     }
 
     #[test]
@@ -252,6 +317,25 @@ mod tests {
         assert!(s.value.as_str().unwrap() == "abc");
         assert!(s.next.len() != 0);
         assert!(s.next[0] == b'd');
+
+        // a declared length past the end of the buffer must error, not index
+        // out of bounds, and a length near usize::MAX must not overflow
+        parse_str(b"2000000000:teste").expect_err("declared length exceeds buffer");
+        parse_str(b"18446744073709551615:x").expect_err("declared length exceeds buffer");
+        parse_str(b"-3:abc").expect_err("negative length");
+    }
+
+    #[test]
+    fn str_raw_bytes() {
+        // the 20-byte SHA-1 "pieces" field in a real .torrent is not valid UTF-8
+        let raw: &[u8] = &[0xff, 0xfe, b'a', 0x00];
+        let mut payload = format!("{}:", raw.len()).into_bytes();
+        payload.extend_from_slice(raw);
+
+        let s = parse_str(&payload).expect("format");
+        assert!(s.value.as_bytes().unwrap() == raw);
+        assert!(s.value.as_str().is_none());
+        assert!(s.value.as_str_lossy().unwrap() == String::from_utf8_lossy(raw));
     }
 
     #[test]
@@ -278,7 +362,7 @@ mod tests {
         let dict = dict.value.as_dict().expect("must be a dict");
         assert!(dict.len() == 1);
         assert!(
-            dict.get("abc")
+            dict.get(&b"abc"[..])
                 .expect("must exist")
                 .as_int()
                 .expect("must be an int")
@@ -0,0 +1,910 @@
+//! Serde support, enabled via the `serde` cargo feature.
+//!
+//! This does not round-trip through [`BencodeType`](crate::BencodeType) at
+//! all: [`Serializer`] writes bencode bytes straight from whatever type the
+//! caller derives `Serialize` for, and [`Deserializer`] decodes straight
+//! into the target type off the raw `&[u8]`, reusing the same
+//! [`next_rule`](crate::next_rule) state machine the core parser uses for
+//! leaf values (integers and strings) and their zero-copy `&[u8]`/`&str`
+//! borrows. Lists and dicts are never collected into an intermediate
+//! `BencodeType::List`/`Dictionary` first: `Deserializer` walks their
+//! elements one at a time straight off the input buffer, so a struct field
+//! only ever materializes as much of a nested container as it actually asks
+//! for.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Write};
+
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{
+    self, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{next_rule, BencodeType, ConvertError};
+
+#[derive(Debug)]
+pub enum Error {
+    Message(String),
+    Bencode(ConvertError),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Message(msg) => f.write_str(msg),
+            Error::Bencode(e) => write!(f, "{:?}", e),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ConvertError> for Error {
+    fn from(e: ConvertError) -> Self {
+        Error::Bencode(e)
+    }
+}
+
+impl Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::custom(msg)
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::custom(msg)
+    }
+}
+
+// --- Serializer -------------------------------------------------------
+
+/// Writes a value as bencode bytes.
+pub struct Serializer<W> {
+    writer: W,
+}
+
+impl<W: Write> Serializer<W> {
+    pub fn new(writer: W) -> Self {
+        Serializer { writer }
+    }
+}
+
+pub fn to_writer<T, W>(value: &T, writer: &mut W) -> Result<(), Error>
+where
+    T: Serialize + ?Sized,
+    W: Write,
+{
+    value.serialize(&mut Serializer::new(writer))
+}
+
+pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut buf = Vec::new();
+    to_writer(value, &mut buf)?;
+    Ok(buf)
+}
+
+const NO_NULL: &str = "bencode has no representation for null/unit/bool/float values";
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer<'a, W>;
+    type SerializeTuple = SeqSerializer<'a, W>;
+    type SerializeTupleStruct = SeqSerializer<'a, W>;
+    type SerializeTupleVariant = SeqSerializer<'a, W>;
+    type SerializeMap = MapSerializer<'a, W>;
+    type SerializeStruct = MapSerializer<'a, W>;
+    type SerializeStructVariant = MapSerializer<'a, W>;
+
+    fn serialize_bool(self, _v: bool) -> Result<(), Error> {
+        Err(Error::custom(NO_NULL))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        write!(self.writer, "i{}e", v)?;
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        i64::try_from(v)
+            .map_err(|_| Error::custom("integer does not fit in an i64"))
+            .and_then(|v| self.serialize_i64(v))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), Error> {
+        Err(Error::custom(NO_NULL))
+    }
+    fn serialize_f64(self, _v: f64) -> Result<(), Error> {
+        Err(Error::custom(NO_NULL))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        write!(self.writer, "{}:", v.len())?;
+        self.writer.write_all(v)?;
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Err(Error::custom(NO_NULL))
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Err(Error::custom(NO_NULL))
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Err(Error::custom(NO_NULL))
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let mut map = self.serialize_map(Some(1))?;
+        map.serialize_entry(variant, value)?;
+        SerializeMap::end(map)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<SeqSerializer<'a, W>, Error> {
+        self.writer.write_all(b"l")?;
+        Ok(SeqSerializer { ser: self })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a, W>, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a, W>, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SeqSerializer<'a, W>, Error> {
+        write!(self.writer, "d{}:{}l", variant.len(), variant)?;
+        Ok(SeqSerializer { ser: self })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer<'a, W>, Error> {
+        self.writer.write_all(b"d")?;
+        Ok(MapSerializer {
+            ser: self,
+            entries: Vec::new(),
+            next_key: None,
+            close_extra: false,
+        })
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer<'a, W>, Error> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer<'a, W>, Error> {
+        write!(self.writer, "d{}:{}d", variant.len(), variant)?;
+        Ok(MapSerializer {
+            ser: self,
+            entries: Vec::new(),
+            next_key: None,
+            close_extra: true,
+        })
+    }
+}
+
+/// Drives `l...e` for sequences, tuples and (after its own `variant` prefix)
+/// tuple variants. Elements stream straight to the writer since bencode
+/// lists carry no length prefix.
+pub struct SeqSerializer<'a, W> {
+    ser: &'a mut Serializer<W>,
+}
+
+impl<'a, W: Write> SerializeSeq for SeqSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.ser.writer.write_all(b"e")?;
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> SerializeTuple for SeqSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<(), Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl<'a, W: Write> SerializeTupleStruct for SeqSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<(), Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl<'a, W: Write> SerializeTupleVariant for SeqSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<(), Error> {
+        // closes the inner list *and* the `{variant: [...]}` wrapper dict
+        self.ser.writer.write_all(b"ee")?;
+        Ok(())
+    }
+}
+
+/// Drives `d...e` for maps, structs and struct variants. Bencode requires
+/// dictionary keys in ascending byte order, so entries are buffered and
+/// sorted by their raw key content before anything is written out.
+pub struct MapSerializer<'a, W> {
+    ser: &'a mut Serializer<W>,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    next_key: Option<Vec<u8>>,
+    /// Set for struct variants: one extra `e` closes the `{variant: {...}}`
+    /// wrapper dict opened by `serialize_struct_variant`.
+    close_extra: bool,
+}
+
+impl<'a, W: Write> MapSerializer<'a, W> {
+    fn push_entry(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        self.entries.push((key, value));
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        let mut entries = self.entries;
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (key, value) in &entries {
+            write!(self.ser.writer, "{}:", key.len())?;
+            self.ser.writer.write_all(key)?;
+            self.ser.writer.write_all(value)?;
+        }
+        self.ser.writer.write_all(b"e")?;
+        if self.close_extra {
+            self.ser.writer.write_all(b"e")?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes only a dictionary key: bencode keys are byte strings, so this
+/// captures just the raw bytes a key's `str`/`bytes` impl produces, without
+/// the length prefix, keeping it directly comparable for canonical sort
+/// order.
+struct KeySerializer;
+
+fn key_must_be_a_string() -> Error {
+    Error::custom("bencode dictionary keys must serialize to a string or byte string")
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Vec<u8>;
+    type Error = Error;
+    type SerializeSeq = ser::Impossible<Vec<u8>, Error>;
+    type SerializeTuple = ser::Impossible<Vec<u8>, Error>;
+    type SerializeTupleStruct = ser::Impossible<Vec<u8>, Error>;
+    type SerializeTupleVariant = ser::Impossible<Vec<u8>, Error>;
+    type SerializeMap = ser::Impossible<Vec<u8>, Error>;
+    type SerializeStruct = ser::Impossible<Vec<u8>, Error>;
+    type SerializeStructVariant = ser::Impossible<Vec<u8>, Error>;
+
+    fn serialize_str(self, v: &str) -> Result<Vec<u8>, Error> {
+        Ok(v.as_bytes().to_vec())
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(v.to_vec())
+    }
+    fn serialize_char(self, v: char) -> Result<Vec<u8>, Error> {
+        self.serialize_str(&v.to_string())
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Vec<u8>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_i8(self, _v: i8) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_i16(self, _v: i16) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_i32(self, _v: i32) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_i64(self, _v: i64) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_u8(self, _v: u8) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_u16(self, _v: u16) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_u32(self, _v: u32) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_u64(self, _v: u64) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_f32(self, _v: f32) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_f64(self, _v: f64) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_none(self) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Vec<u8>, Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Vec<u8>, Error> {
+        self.serialize_str(variant)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Vec<u8>, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(key_must_be_a_string())
+    }
+}
+
+impl<'a, W: Write> SerializeMap for MapSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .next_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.push_entry(key, to_bytes(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> SerializeStruct for MapSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.push_entry(key.as_bytes().to_vec(), to_bytes(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> SerializeStructVariant for MapSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+// --- Deserializer -------------------------------------------------------
+
+/// Decodes a value straight from bencode bytes, borrowing strings from the
+/// input exactly like [`crate::parse`] does.
+pub struct Deserializer<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn from_bytes(input: &'de [u8]) -> Self {
+        Deserializer { input }
+    }
+}
+
+pub fn from_bytes<'de, T>(input: &'de [u8]) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(&mut Deserializer::from_bytes(input))
+}
+
+impl<'de> Deserializer<'de> {
+    /// Parses one leaf value (an integer or a string) and advances `input`
+    /// past it. Lists and dicts are *not* leaves: they are walked element by
+    /// element straight off `input` by [`LazySeq`]/[`LazyMap`] instead, so
+    /// that decoding a container never materializes more of it than the
+    /// target type actually asks for.
+    fn next_value(&mut self) -> Result<BencodeType<'de>, Error> {
+        let res = next_rule(self.input)?;
+        self.input = res.next;
+        Ok(res.value)
+    }
+
+    /// Consumes a single expected byte, e.g. the `e` that closes the
+    /// single-entry wrapper dict around a struct/tuple/newtype enum variant.
+    fn expect_byte(&mut self, expected: u8) -> Result<(), Error> {
+        match self.input.first() {
+            Some(&b) if b == expected => {
+                self.input = &self.input[1..];
+                Ok(())
+            }
+            _ => Err(Error::custom("malformed bencode: unexpected byte")),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.input.first() {
+            Some(b'i') => self.deserialize_i64(visitor),
+            Some(b'l') => self.deserialize_seq(visitor),
+            Some(b'd') => self.deserialize_map(visitor),
+            Some(b'0'..=b'9') => self.deserialize_bytes(visitor),
+            _ => Err(Error::custom("expected a bencode value")),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // bencode has no null marker: every present value deserializes as `Some`.
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.next_value()? {
+            BencodeType::String(bytes) => visitor.visit_borrowed_bytes(bytes),
+            _ => Err(Error::custom("expected a bencode string")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.next_value()? {
+            BencodeType::String(bytes) => {
+                let s = std::str::from_utf8(bytes)
+                    .map_err(|_| Error::custom("bencode string is not valid UTF-8"))?;
+                visitor.visit_borrowed_str(s)
+            }
+            _ => Err(Error::custom("expected a bencode string")),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.input.first() {
+            Some(b'l') => {
+                self.input = &self.input[1..];
+                visitor.visit_seq(LazySeq { de: self })
+            }
+            _ => Err(Error::custom("expected a bencode list")),
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.input.first() {
+            Some(b'd') => {
+                self.input = &self.input[1..];
+                visitor.visit_map(LazyMap { de: self })
+            }
+            _ => Err(Error::custom("expected a bencode dictionary")),
+        }
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.next_value()? {
+            BencodeType::Integer(i) => visitor.visit_i64(i),
+            _ => Err(Error::custom("expected a bencode integer")),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.input.first() {
+            Some(b'd') => {
+                self.input = &self.input[1..];
+                visitor.visit_enum(DictEnumDeserializer { de: self })
+            }
+            Some(b'0'..=b'9') => visitor.visit_enum(UnitEnumDeserializer { de: self }),
+            _ => Err(Error::custom(
+                "expected a bencode string or dictionary for an enum",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 u8 u16 u32 u64 f32 f64 char string
+        byte_buf unit unit_struct tuple
+        tuple_struct struct identifier ignored_any
+    }
+}
+
+/// Drives [`SeqAccess`] straight off `de.input`: each element is decoded by
+/// recursing into `de` itself, and the list's own `e` terminator is consumed
+/// in place without ever buffering the list's elements.
+struct LazySeq<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'a, 'de> SeqAccess<'de> for LazySeq<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.de.input.first() == Some(&b'e') {
+            self.de.input = &self.de.input[1..];
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+}
+
+/// Drives [`MapAccess`] straight off `de.input`, the dict counterpart of
+/// [`LazySeq`]: keys and values are decoded by recursing into `de`, one
+/// entry at a time, rather than collecting the whole dict up front.
+struct LazyMap<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'a, 'de> MapAccess<'de> for LazyMap<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.de.input.first() == Some(&b'e') {
+            self.de.input = &self.de.input[1..];
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+/// Decodes a unit enum variant, encoded as a bare bencode string.
+struct UnitEnumDeserializer<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'a, 'de> de::EnumAccess<'de> for UnitEnumDeserializer<'a, 'de> {
+    type Error = Error;
+    type Variant = UnitEnumDeserializer<'a, 'de>;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, Self::Variant), Error> {
+        let name = match self.de.next_value()? {
+            BencodeType::String(bytes) => std::str::from_utf8(bytes)
+                .map_err(|_| Error::custom("enum variant name is not valid UTF-8"))?,
+            _ => return Err(Error::custom("expected a bencode string for an enum variant")),
+        };
+        let variant = seed.deserialize(de::value::StrDeserializer::<Error>::new(name))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'a, 'de> de::VariantAccess<'de> for UnitEnumDeserializer<'a, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, _seed: T) -> Result<T::Value, Error> {
+        Err(Error::custom("unit variant must not carry a payload"))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error::custom("unit variant must not carry a payload"))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Error> {
+        Err(Error::custom("unit variant must not carry a payload"))
+    }
+}
+
+/// Decodes a newtype/tuple/struct enum variant, encoded as a single-entry
+/// dictionary mapping the variant name to its payload. The dict's opening
+/// `d` has already been consumed by `deserialize_enum`; what's left once the
+/// variant name and payload are decoded is exactly one closing `e`.
+struct DictEnumDeserializer<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'a, 'de> de::EnumAccess<'de> for DictEnumDeserializer<'a, 'de> {
+    type Error = Error;
+    type Variant = DictEnumDeserializer<'a, 'de>;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, Self::Variant), Error> {
+        let name = match self.de.next_value()? {
+            BencodeType::String(bytes) => std::str::from_utf8(bytes)
+                .map_err(|_| Error::custom("enum variant name is not valid UTF-8"))?,
+            _ => return Err(Error::custom("expected a bencode string for an enum variant")),
+        };
+        let variant = seed.deserialize(de::value::StrDeserializer::<Error>::new(name))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'a, 'de> de::VariantAccess<'de> for DictEnumDeserializer<'a, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Err(Error::custom(
+            "unit variant must not be wrapped in a dictionary",
+        ))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let value = seed.deserialize(&mut *self.de)?;
+        self.de.expect_byte(b'e')?;
+        Ok(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let value = de::Deserializer::deserialize_tuple(&mut *self.de, len, visitor)?;
+        self.de.expect_byte(b'e')?;
+        Ok(value)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let value = de::Deserializer::deserialize_struct(&mut *self.de, "", fields, visitor)?;
+        self.de.expect_byte(b'e')?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct File {
+        path: String,
+        length: i64,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        Single,
+        Multi(i64),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Torrent {
+        name: String,
+        files: Vec<File>,
+        kind: Kind,
+    }
+
+    #[test]
+    fn roundtrip_struct() {
+        let torrent = Torrent {
+            name: "abc".to_string(),
+            files: vec![
+                File { path: "a".into(), length: 10 },
+                File { path: "b".into(), length: 20 },
+            ],
+            kind: Kind::Multi(42),
+        };
+
+        let bytes = to_bytes(&torrent).expect("should serialize");
+        let back: Torrent = from_bytes(&bytes).expect("should deserialize");
+        assert!(back == torrent);
+    }
+
+    #[test]
+    fn dict_keys_are_sorted() {
+        let torrent = Torrent {
+            name: "x".into(),
+            files: vec![],
+            kind: Kind::Single,
+        };
+
+        let bytes = to_bytes(&torrent).expect("should serialize");
+        // struct fields serialize as a dict, so keys come out in sorted
+        // byte order (`files` < `kind` < `name`) regardless of field order.
+        assert!(bytes == b"d5:filesle4:kind6:Single4:name1:xe");
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap as Map;
+
+use crate::decoder::scan_value;
+use crate::{next_rule, BencodeType, ConvertError};
+
+/// Maps each key of a top-level bencode dictionary to the exact sub-slice of
+/// the original input its value was parsed from.
+///
+/// This is what a caller needs for something like a torrent's `info_hash`:
+/// the hash must cover the literal source bytes of the `info` entry, not a
+/// re-encoding of it (canonicalization, even if byte-identical in practice,
+/// is not something to rely on for a hash that has to match other clients).
+pub type SpanMap<'a> = Map<&'a [u8], &'a [u8]>;
+
+/// Parses `stream` like [`crate::parse`], additionally returning a
+/// [`SpanMap`] of the top-level dictionary's entries, if the parsed value is
+/// a dictionary. For any other top-level value the map is empty.
+///
+/// This walks a top-level dict once: its entries' spans are recorded in the
+/// same pass that builds the returned `BencodeType::Dictionary`, rather than
+/// parsing the whole input a second time to get the value tree.
+pub fn parse_with_spans(stream: &[u8]) -> Result<(BencodeType, SpanMap), ConvertError> {
+    match stream.first() {
+        Some(b'd') => dict_with_spans(stream),
+        _ => Ok((crate::parse(stream)?, Map::new())),
+    }
+}
+
+/// Like [`parse_with_spans`], but for callers (e.g. [`crate::info_hash`])
+/// that only need the spans and would otherwise immediately throw away the
+/// parsed value tree. Entry values are never turned into a `BencodeType` at
+/// all here — [`scan_value`] only measures how many bytes each one takes.
+pub(crate) fn dict_spans(stream: &[u8]) -> Result<SpanMap, ConvertError> {
+    let mut rest = &stream[1..];
+    let mut spans = Map::new();
+
+    while !rest.is_empty() && rest[0] != b'e' {
+        let key = next_rule(rest)?;
+        let value_start = key.next;
+        let value_len = scan_value(value_start)?.ok_or(ConvertError::EOF)?;
+
+        match key.value {
+            BencodeType::String(k) => {
+                spans.insert(k, &value_start[..value_len]);
+                rest = &value_start[value_len..];
+            }
+            _ => return Err(ConvertError::InvalidFormat),
+        }
+    }
+
+    if rest.is_empty() || rest[0] != b'e' {
+        return Err(ConvertError::InvalidFormat);
+    }
+    require_fully_consumed(&rest[1..])?;
+    Ok(spans)
+}
+
+/// Walks a top-level `d...e` the same way `parse_dict` does, but also
+/// records the consumed sub-slice of each entry's value (its span) while
+/// building the same `BencodeType::Dictionary` `crate::parse` would. A span
+/// is simply the part of `stream` that `next_rule` consumed, found the same
+/// way the rest of the parser advances through the input: by comparing a
+/// slice to what is left of it once a rule is done.
+fn dict_with_spans(stream: &[u8]) -> Result<(BencodeType, SpanMap), ConvertError> {
+    let mut rest = &stream[1..];
+    let mut entries = Map::new();
+    let mut spans = Map::new();
+
+    while !rest.is_empty() && rest[0] != b'e' {
+        let key = next_rule(rest)?;
+        let value_start = key.next;
+        let entry = next_rule(value_start)?;
+
+        match key.value {
+            BencodeType::String(k) => {
+                let span = &value_start[..value_start.len() - entry.next.len()];
+                spans.insert(k, span);
+                entries.insert(k, entry.value);
+                rest = entry.next;
+            }
+            _ => return Err(ConvertError::InvalidFormat),
+        }
+    }
+
+    if rest.is_empty() || rest[0] != b'e' {
+        return Err(ConvertError::InvalidFormat);
+    }
+    require_fully_consumed(&rest[1..])?;
+    Ok((BencodeType::Dictionary(entries), spans))
+}
+
+fn require_fully_consumed(rest: &[u8]) -> Result<(), ConvertError> {
+    if rest.is_empty() {
+        Ok(())
+    } else {
+        Err(ConvertError::EOF)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn spans_top_level_entries() {
+        let bencode = b"d4:infod6:lengthi10ee3:fooi1ee";
+        let (value, spans) = parse_with_spans(bencode).expect("should parse");
+        assert!(value.as_dict().expect("top level is a dict").len() == 2);
+        assert!(spans.get(&b"info"[..]).unwrap() == &&b"d6:lengthi10ee"[..]);
+        assert!(spans.get(&b"foo"[..]).unwrap() == &&b"i1e"[..]);
+    }
+
+    #[test]
+    fn empty_span_map_for_non_dict() {
+        let (value, spans) = parse_with_spans(b"i3e").expect("should parse");
+        assert!(value == BencodeType::Integer(3));
+        assert!(spans.is_empty());
+    }
+
+    #[test]
+    fn dict_spans_matches_parse_with_spans() {
+        let bencode = b"d4:infod6:lengthi10ee3:fooi1ee";
+        let spans = dict_spans(bencode).expect("should parse");
+        assert!(spans.get(&b"info"[..]).unwrap() == &&b"d6:lengthi10ee"[..]);
+        assert!(spans.get(&b"foo"[..]).unwrap() == &&b"i1e"[..]);
+    }
+}